@@ -0,0 +1,35 @@
+//! Guards against the per-node `O(n^2)` buffer clone/rebuild that used
+//! to dominate parsing large scene files, in the spirit of
+//! rust-analyzer's `benchmark_parser`. Wired up via a `[[bench]]` entry
+//! (`harness = false`) against `criterion`.
+
+use std::io::Cursor;
+
+use arse::ArseParser;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+/// A large synthetic `.ass` source: `n` simple sphere nodes, each with
+/// a handful of scalar parameters.
+fn synthetic_source(n: usize) -> String {
+    let mut out = String::with_capacity(n * 64);
+    for i in 0..n {
+        out.push_str(&format!(
+            "sphere\n{{\n    name Sphere{:05}\n    radius 1.0\n    visible true\n}}\n",
+            i
+        ));
+    }
+    out
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let source = synthetic_source(50_000);
+    c.bench_function("parse_50k_nodes", |b| {
+        b.iter(|| {
+            let parser = ArseParser::new(Cursor::new(source.as_bytes()));
+            assert_eq!(parser.count(), 50_000);
+        })
+    });
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
@@ -0,0 +1,109 @@
+//! A seekable index over a parsed `.ass` file's top-level nodes,
+//! inspired by the NAR `.ls` listing format: one streaming pass
+//! ([`crate::ArseParser::build_index`]) records each node's name, type
+//! and byte range, so a later lookup can jump straight to it instead of
+//! re-parsing the whole file.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::{build_node, node_parser, Node};
+
+/// One entry in a [`NodeIndex`]: a top-level node's identity and the
+/// byte range spanning its full text, from the start of its type name
+/// through the closing `}` of its body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexEntry {
+    pub name: String,
+    pub node_type: String,
+    pub offset: u64,
+    pub length: u64,
+}
+
+/// A listing of every top-level node found while indexing a `.ass`
+/// file.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NodeIndex {
+    pub entries: Vec<IndexEntry>,
+}
+
+/// Seeks `reader` to `entry`'s byte range and parses just that one
+/// node, without re-reading the rest of the file.
+pub fn get<R: Read + Seek>(reader: &mut R, entry: &IndexEntry) -> Option<Node> {
+    reader.seek(SeekFrom::Start(entry.offset)).ok()?;
+    read_entry(reader, entry)
+}
+
+/// Fallback for inputs that can't be seeked, such as a `GzDecoder`:
+/// gzip can only be decompressed forward, so each lookup reads and
+/// discards bytes to reach the next entry instead of seeking. Tracks
+/// how much of the stream has already been consumed, so a second
+/// lookup against the same reader skips only the remaining delta
+/// rather than re-measuring from the start.
+///
+/// Entries must be requested in non-decreasing offset order, since the
+/// underlying reader can't rewind.
+pub struct UnseekableIndex<R: Read> {
+    reader: R,
+    consumed: u64,
+}
+
+impl<R: Read> UnseekableIndex<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, consumed: 0 }
+    }
+
+    /// Reads forward to `entry`'s byte range and parses just that one
+    /// node.
+    pub fn get(&mut self, entry: &IndexEntry) -> Option<Node> {
+        let skip = entry.offset.checked_sub(self.consumed)?;
+        io::copy(&mut self.reader.by_ref().take(skip), &mut io::sink()).ok()?;
+        self.consumed += skip;
+        let node = read_entry(&mut self.reader, entry)?;
+        self.consumed += entry.length;
+        Some(node)
+    }
+}
+
+fn read_entry<R: Read>(reader: &mut R, entry: &IndexEntry) -> Option<Node> {
+    let mut buf = vec![0u8; entry.length as usize];
+    reader.read_exact(&mut buf).ok()?;
+    let text = std::str::from_utf8(&buf).ok()?;
+    let (_, (node_type, parameters)) = node_parser(text).ok()?;
+    Some(build_node(node_type, parameters))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ArseParser;
+    use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+    use indoc::indoc;
+    use std::io::Write;
+
+    #[test]
+    fn repeated_lookups_against_one_gzip_reader() {
+        let data = indoc! {"
+        sphere
+        {
+            name Sphere01
+        }
+        box
+        {
+            name Box01
+        }
+        "};
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let index = ArseParser::new(data.as_bytes()).build_index();
+        assert_eq!(index.entries.len(), 2);
+
+        let mut unseekable = UnseekableIndex::new(GzDecoder::new(gzipped.as_slice()));
+        let first = unseekable.get(&index.entries[0]).unwrap();
+        assert_eq!(first.name, "Sphere01");
+        let second = unseekable.get(&index.entries[1]).unwrap();
+        assert_eq!(second.name, "Box01");
+    }
+}
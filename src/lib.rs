@@ -1,24 +1,40 @@
 use nom::{
     branch::alt,
-    bytes::streaming::{is_not, tag, take_until, take_while},
-    character::streaming::char,
-    combinator::map,
+    bytes::streaming::{is_not, tag, take_while, take_while1},
+    character::streaming::{char, digit1, satisfy},
+    combinator::{map, not, opt, recognize},
     error::ParseError,
-    sequence::{delimited, pair, preceded},
+    multi::{count, many0, many1},
+    sequence::{delimited, pair, preceded, terminated, tuple},
     Err, IResult,
 };
 
 use std::{
-    ffi::OsStr,
     fs::File,
     io::{BufRead, BufReader, Read},
     path::Path,
     str::Utf8Error,
 };
 
+pub mod index;
+pub use index::{IndexEntry, NodeIndex, UnseekableIndex};
+
+/// Drop already-parsed bytes once they make up at least this much of
+/// the buffer, rather than compacting after every single node. This
+/// keeps compaction an amortized cost over the whole file instead of
+/// an O(n^2) `drain` per node.
+const COMPACT_THRESHOLD: usize = 64 * 1024;
+
 pub struct ArseParser<R: Read> {
     reader: BufReader<R>,
+    /// The growing source buffer. Bytes before `cursor` have already
+    /// been parsed but aren't dropped until `compact` runs.
     buffer: String,
+    /// Read position into `buffer`: the next unparsed byte.
+    cursor: usize,
+    /// Byte offset, into the whole source, of `buffer`'s first byte.
+    base_offset: usize,
+    errors: Vec<SyntaxError>,
 }
 
 impl<R: Read> ArseParser<R> {
@@ -26,13 +42,19 @@ impl<R: Read> ArseParser<R> {
         Self {
             reader: BufReader::new(reader),
             buffer: String::new(),
+            cursor: 0,
+            base_offset: 0,
+            errors: Vec::new(),
         }
     }
 
     pub fn with_capacity(reader: R, capacity: usize) -> Self {
         Self {
-            reader: BufReader::with_capacity(4 * 1024 * 1024, reader),
+            reader: BufReader::with_capacity(capacity, reader),
             buffer: String::new(),
+            cursor: 0,
+            base_offset: 0,
+            errors: Vec::new(),
         }
     }
 
@@ -46,6 +68,123 @@ impl<R: Read> ArseParser<R> {
         }
         Ok(nb)
     }
+
+    /// Byte offset, into the whole source, of the next unparsed byte.
+    fn consumed(&self) -> usize {
+        self.base_offset + self.cursor
+    }
+
+    /// The not-yet-parsed tail of `buffer`.
+    fn unparsed(&self) -> &str {
+        &self.buffer[self.cursor..]
+    }
+
+    /// Drops the already-parsed prefix of `buffer` once it's grown
+    /// large enough to be worth the `drain`, keeping memory use bounded
+    /// without paying the cost on every node.
+    fn compact(&mut self) {
+        if self.cursor >= COMPACT_THRESHOLD || self.cursor * 2 >= self.buffer.len() {
+            self.buffer.drain(..self.cursor);
+            self.base_offset += self.cursor;
+            self.cursor = 0;
+        }
+    }
+
+    /// Syntax errors recorded so far. A malformed node does not stop
+    /// iteration: the parser resynchronizes at the next plausible node
+    /// start and records the skipped span here instead.
+    pub fn errors(&self) -> &[SyntaxError] {
+        &self.errors
+    }
+
+    /// Parses to completion and serializes the resulting nodes and any
+    /// recorded syntax errors into a stable, human-readable dump. Used
+    /// by the corpus snapshot tests.
+    pub fn debug_dump(&mut self) -> String {
+        let mut out = String::new();
+        for node in self.by_ref() {
+            out.push_str(&format!("{:?}\n", node));
+        }
+        for error in self.errors() {
+            out.push_str(&format!("ERROR {:?}\n", error));
+        }
+        out
+    }
+
+    /// Handles the error branch shared by `next` and `build_index`:
+    /// records a `SyntaxError` for the unparseable span and skips to
+    /// the next plausible node. Returns `true` if no more progress is
+    /// possible (end of input reached without finding one).
+    fn recover_from_error(&mut self, read: usize) -> bool {
+        match find_recovery_point(self.unparsed()) {
+            Some(skip) => {
+                self.errors.push(SyntaxError {
+                    message: "failed to parse node".to_owned(),
+                    range: (self.consumed(), self.consumed() + skip),
+                });
+                self.cursor += skip;
+                self.compact();
+                false
+            }
+            None if read == 0 => {
+                self.errors.push(SyntaxError {
+                    message: "failed to parse node".to_owned(),
+                    range: (self.consumed(), self.base_offset + self.buffer.len()),
+                });
+                true
+            }
+            // More input might still reveal the next node start.
+            None => false,
+        }
+    }
+
+    /// Performs one streaming pass over the source and returns a
+    /// [`NodeIndex`] giving every top-level node's byte range, without
+    /// retaining the parsed nodes themselves. Pair with [`index::get`]
+    /// (or [`index::UnseekableIndex`] for inputs that can't be seeked)
+    /// to later jump straight to one node instead of re-reading the
+    /// whole file.
+    pub fn build_index(&mut self) -> NodeIndex {
+        let mut entries = Vec::new();
+        loop {
+            let read = self.fill().unwrap();
+            if self.unparsed().is_empty() {
+                break;
+            }
+            let leading_ws = spacelike::<nom::error::Error<&str>>(self.unparsed())
+                .map(|(_, ws)| ws.len())
+                .unwrap_or(0);
+            match root(self.unparsed()) {
+                Ok((rest, RootElement::Node(node))) => {
+                    let start = self.consumed() + leading_ws;
+                    self.cursor = self.buffer.len() - rest.len();
+                    self.compact();
+                    let end = self.consumed();
+                    entries.push(IndexEntry {
+                        name: node.name,
+                        node_type: node.node_type,
+                        offset: start as u64,
+                        length: (end - start) as u64,
+                    });
+                }
+                Ok((rest, RootElement::Comment(_))) => {
+                    self.cursor = self.buffer.len() - rest.len();
+                    self.compact();
+                }
+                Err(Err::Incomplete(_)) => {
+                    if read == 0 {
+                        break;
+                    }
+                }
+                Err(_) => {
+                    if self.recover_from_error(read) {
+                        break;
+                    }
+                }
+            }
+        }
+        NodeIndex { entries }
+    }
 }
 
 impl<R: Read> Iterator for ArseParser<R> {
@@ -54,23 +193,29 @@ impl<R: Read> Iterator for ArseParser<R> {
     fn next(&mut self) -> Option<Self::Item> {
         loop {
             let read = self.fill().unwrap();
-            if self.buffer.is_empty() {
+            if self.unparsed().is_empty() {
                 return None;
             }
-            match root(self.buffer.clone().as_str()) {
+            match root(self.unparsed()) {
                 Ok((rest, RootElement::Node(node))) => {
-                    self.buffer = rest.to_string();
+                    self.cursor = self.buffer.len() - rest.len();
+                    self.compact();
                     return Some(node);
                 }
                 Ok((rest, RootElement::Comment(_))) => {
-                    self.buffer = rest.to_string();
+                    self.cursor = self.buffer.len() - rest.len();
+                    self.compact();
                 }
                 Err(Err::Incomplete(_)) => {
                     if read == 0 {
                         break;
                     }
                 }
-                Err(_) => break,
+                Err(_) => {
+                    if self.recover_from_error(read) {
+                        break;
+                    }
+                }
             }
         }
         None
@@ -81,6 +226,86 @@ impl<R: Read> Iterator for ArseParser<R> {
 pub struct Node {
     pub node_type: String,
     pub name: String,
+    pub parameters: Vec<(String, Value)>,
+}
+
+/// A parameter value found inside a node's `{ ... }` body.
+///
+/// `.ass` files mix plain scalars (`xres 1920`), quoted strings
+/// (`filter "catrom"`) and array literals (`points 4 1 FLOAT ...`,
+/// or the brace-delimited `matrix { ... }`). `Value` covers all of these
+/// so callers don't need to re-parse the body themselves.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    String(String),
+    Array(Vec<Value>),
+}
+
+/// A parse failure recorded at a byte range into the source, in the
+/// spirit of rust-analyzer's error model: a bad node doesn't abort the
+/// parse, it just leaves a gap and a reason behind.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub message: String,
+    /// Byte offsets `(start, end)` into the original source.
+    pub range: (usize, usize),
+}
+
+/// Converts a byte offset into `source` to a 1-based `(line, column)`
+/// pair, so a `SyntaxError`'s range can be pointed at in an editor.
+pub fn offset_to_line_col(source: &str, offset: usize) -> (usize, usize) {
+    let mut line = 1;
+    let mut col = 1;
+    for (idx, c) in source.char_indices() {
+        if idx >= offset {
+            break;
+        }
+        if c == '\n' {
+            line += 1;
+            col = 1;
+        } else {
+            col += 1;
+        }
+    }
+    (line, col)
+}
+
+/// Scans `i` for the next plausible top-level node start: an identifier
+/// immediately followed (modulo whitespace) by `{`, at brace depth zero.
+/// Used to resynchronize after a node fails to parse, so one malformed
+/// node doesn't blind the rest of the file. Never returns `0`, so
+/// callers are guaranteed forward progress.
+///
+/// A candidate is only accepted at a genuine word boundary (right after
+/// whitespace or a brace) — otherwise a truncated
+/// prefix of the current malformed node's own type name (e.g. `phere`
+/// inside a broken `sphere\n{...}`) could satisfy the `name`-followed-
+/// by-`{` check without the scan having actually left that node,
+/// producing a cascade of bogus single-byte error ranges instead of
+/// skipping past it.
+fn find_recovery_point(i: &str) -> Option<usize> {
+    let mut depth: i32 = 0;
+    let mut at_boundary = true;
+    for (idx, c) in i.char_indices() {
+        let is_boundary = at_boundary;
+        at_boundary = SPACELIKE_CHARS.contains(c) || c == '{' || c == '}';
+        match c {
+            '{' => depth += 1,
+            '}' => depth = (depth - 1).max(0),
+            c if idx > 0 && is_boundary && depth == 0 && !SPACELIKE_CHARS.contains(c) => {
+                if let Ok((rest, _)) = pair(name, spacelike)(&i[idx..]) {
+                    if rest.starts_with('{') {
+                        return Some(idx);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    None
 }
 
 /// Comments are lines starting with a hash
@@ -101,22 +326,132 @@ fn name(i: &str) -> nom::IResult<&str, &str> {
     is_not(" \t\r\n{}[]()")(i)
 }
 
-/// Name parameter preceded by `name `
-fn node_name(i: &str) -> nom::IResult<&str, &str> {
-    delimited(tag("name "), name, spacelike)(i)
+/// True for any character that can continue a bareword/number/bool
+/// token — the same set `name` accepts.
+fn is_word_char(c: char) -> bool {
+    !SPACELIKE_CHARS.contains(c) && !"{}[]()".contains(c)
+}
+
+/// Zero-width assertion: succeeds only at a word boundary (the next
+/// character isn't itself word-constituent); at the end of the input
+/// it returns `Incomplete`, asking for more. Used
+/// to stop `boolean`/`number` from matching just a prefix of the next
+/// bareword, e.g. parsing `truest` as `Bool(true)` plus a leftover
+/// `"st"`, or `123abc` as a truncated number.
+fn word_boundary(i: &str) -> nom::IResult<&str, ()> {
+    not(satisfy(is_word_char))(i)
 }
 
-/// The node body, we only parse the name parameter for now
-fn node_body(i: &str) -> nom::IResult<&str, &str> {
-    delimited(
-        char('{'),
-        delimited(take_until("name "), node_name, take_until("}")),
-        char('}'),
+/// An integer or floating point literal, disambiguated by the presence
+/// of a decimal point or exponent.
+fn number(i: &str) -> nom::IResult<&str, Value> {
+    terminated(
+        map(
+            recognize(tuple((
+                opt(char('-')),
+                digit1,
+                opt(pair(char('.'), digit1)),
+                opt(tuple((
+                    alt((char('e'), char('E'))),
+                    opt(alt((char('+'), char('-')))),
+                    digit1,
+                ))),
+            ))),
+            |s: &str| {
+                if s.contains('.') || s.contains('e') || s.contains('E') {
+                    Value::Float(s.parse().unwrap())
+                } else {
+                    Value::Int(s.parse().unwrap())
+                }
+            },
+        ),
+        word_boundary,
+    )(i)
+}
+
+/// `true` or `false`.
+fn boolean(i: &str) -> nom::IResult<&str, bool> {
+    terminated(
+        alt((map(tag("true"), |_| true), map(tag("false"), |_| false))),
+        word_boundary,
     )(i)
 }
 
+/// A double-quoted string, e.g. `"catrom"`.
+fn quoted_string(i: &str) -> nom::IResult<&str, &str> {
+    delimited(char('"'), is_not("\""), char('"'))(i)
+}
+
+/// A single scalar value: a bool, number, quoted string, or bareword
+/// (e.g. an unquoted enum-like value such as `filter catrom`).
+fn scalar_value(i: &str) -> nom::IResult<&str, Value> {
+    alt((
+        map(boolean, Value::Bool),
+        number,
+        map(quoted_string, |s| Value::String(s.to_owned())),
+        map(name, |s| Value::String(s.to_owned())),
+    ))(i)
+}
+
+/// An upper-case type tag as used in array headers, e.g. `FLOAT`, `POINT`.
+fn type_tag(i: &str) -> nom::IResult<&str, &str> {
+    take_while1(|c: char| c.is_ascii_uppercase())(i)
+}
+
+/// Arnold's header-style array literal: an element count, a motion key
+/// count, and a type tag, followed by `count` scalar values, e.g.
+/// `4 1 FLOAT 0 0 0 1`.
+fn array_value(i: &str) -> nom::IResult<&str, Value> {
+    let (i, len) = preceded(spacelike, digit1)(i)?;
+    let len: usize = len.parse().unwrap();
+    let (i, _motion_keys) = preceded(spacelike, digit1)(i)?;
+    let (i, _type_tag) = preceded(spacelike, type_tag)(i)?;
+    let (i, values) = count(preceded(spacelike, scalar_value), len)(i)?;
+    Ok((i, Value::Array(values)))
+}
+
+/// A brace-delimited array literal, e.g. the nested-brace `matrix` block.
+fn brace_array(i: &str) -> nom::IResult<&str, Value> {
+    map(
+        delimited(
+            char('{'),
+            many1(preceded(spacelike, scalar_value)),
+            preceded(spacelike, char('}')),
+        ),
+        Value::Array,
+    )(i)
+}
+
+/// The value half of a parameter: a brace array, a header-style array,
+/// or a single scalar. There's no delimiter between one parameter's
+/// value and the next parameter's key, so a scalar value can only ever
+/// be one token — anything greedier would swallow the next key as part of
+/// this value.
+fn parameter_value(i: &str) -> nom::IResult<&str, Value> {
+    alt((
+        preceded(spacelike, brace_array),
+        array_value,
+        preceded(spacelike, scalar_value),
+    ))(i)
+}
+
+/// A single `key value...` parameter inside a node body.
+fn parameter(i: &str) -> nom::IResult<&str, (String, Value)> {
+    let (i, key) = preceded(spacelike, name)(i)?;
+    let (i, value) = parameter_value(i)?;
+    Ok((i, (key.to_owned(), value)))
+}
+
+/// The node body: every `key value` parameter found inside `{ ... }`.
+fn node_body(i: &str) -> nom::IResult<&str, Vec<(String, Value)>> {
+    delimited(char('{'), many0(parameter), preceded(spacelike, char('}')))(i)
+}
+
+/// A node's type name paired with its parsed body.
+type NodeParts<'a> = (&'a str, Vec<(String, Value)>);
+
 /// Node with it's preceding type_name and delegate the body to `node_body`
-fn node_parser(i: &str) -> nom::IResult<&str, (&str, &str)> {
+fn node_parser(i: &str) -> nom::IResult<&str, NodeParts<'_>> {
     pair(preceded(spacelike, name), preceded(spacelike, node_body))(i)
 }
 
@@ -130,32 +465,76 @@ enum RootElement<'a> {
 /// body can contain nodes or comments
 fn root<'a>(i: &'a str) -> IResult<&'a str, RootElement<'a>> {
     alt((
-        map(comment, |c| RootElement::Comment(c)),
-        map(node_parser, |n| {
-            RootElement::Node(Node {
-                node_type: n.0.to_owned(),
-                name: n.1.to_owned(),
-            })
+        map(comment, RootElement::Comment),
+        map(node_parser, |(node_type, parameters)| {
+            RootElement::Node(build_node(node_type, parameters))
         }),
     ))(i)
 }
 
+/// Assembles a `Node` from its type name and parsed parameters, pulling
+/// `name` out of the parameter list for the dedicated `name` field.
+fn build_node(node_type: &str, parameters: Vec<(String, Value)>) -> Node {
+    let name = parameters
+        .iter()
+        .find(|(key, _)| key == "name")
+        .and_then(|(_, value)| match value {
+            Value::String(s) => Some(s.clone()),
+            _ => None,
+        })
+        .unwrap_or_default();
+    Node {
+        node_type: node_type.to_owned(),
+        name,
+        parameters,
+    }
+}
+
 /// Get a buffered reader for filename.
 /// Supports both text and gz files.
 pub fn reader(filename: &str) -> Box<dyn Read> {
     let path = Path::new(filename);
-    let file = match File::open(&path) {
+    let file = match File::open(path) {
         Err(why) => panic!("couldn't open {}, {}", path.display(), why),
         Ok(file) => file,
     };
 
-    // We are only checking for extension right now to
-    // determine if it is a gz file.
-    // TODO: Use other heuristics to determine file type
-    if path.extension() == Some(OsStr::new("gz")) {
-        Box::new(flate2::read::GzDecoder::new(file))
+    // Sniff the gzip magic bytes (1f 8b) instead of trusting the `.gz`
+    // extension, so a mislabeled file still gets decompressed.
+    const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+    let mut buffered = BufReader::new(file);
+    let is_gzip = buffered
+        .fill_buf()
+        .map(|peeked| peeked.starts_with(&GZIP_MAGIC))
+        .unwrap_or(false);
+
+    if is_gzip {
+        Box::new(flate2::read::GzDecoder::new(buffered))
     } else {
-        Box::new(file)
+        Box::new(buffered)
+    }
+}
+
+/// Expands `pattern` (e.g. `scenes/**/*.ass*`) and chains the node
+/// streams of every matching file, read through the same magic-byte
+/// sniffing as [`reader`], into a single iterator. Useful for
+/// batch-processing a whole render directory in one pass.
+pub fn glob_parser(pattern: &str) -> impl Iterator<Item = Node> {
+    glob::glob(pattern)
+        .expect("invalid glob pattern")
+        .filter_map(Result::ok)
+        .flat_map(|path| ArseParser::new(reader(&path.to_string_lossy())))
+}
+
+/// Runs the parser to completion over `text` and asserts that it never
+/// panics and that every recorded [`SyntaxError`]'s range stays within
+/// the bounds of `text`. Entry point for `cargo fuzz`.
+pub fn check_parser(text: &str) {
+    let mut parser = ArseParser::new(text.as_bytes());
+    for _ in &mut parser {}
+    for error in parser.errors() {
+        assert!(error.range.0 <= error.range.1);
+        assert!(error.range.1 <= text.len());
     }
 }
 
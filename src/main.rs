@@ -1,4 +1,4 @@
-mod lib;
+use arse::{reader, ArseParser};
 use std::env;
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -8,7 +8,7 @@ fn main() {
     let filename = args[1].as_str();
     println!("Filename: {}", filename);
 
-    let parser = lib::ArseParser::new(lib::reader(filename));
+    let parser = ArseParser::new(reader(filename));
     for node in parser {
         println!("{}", node.name);
     }
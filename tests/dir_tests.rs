@@ -0,0 +1,52 @@
+//! Corpus-driven snapshot tests, in the spirit of rust-analyzer's
+//! `dir_tests`: every `.ass` fixture under `tests/data/{ok,err}` is
+//! parsed and its `debug_dump()` is compared against a checked-in
+//! `.txt` expectation of the same name. `ok` fixtures must parse
+//! without errors, `err` fixtures must report at least one.
+
+use arse::ArseParser;
+use std::{fs, path::Path};
+
+fn run_corpus(dir: &str, expect_errors: bool) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join(dir);
+    for entry in fs::read_dir(&root).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|e| e.to_str()) != Some("ass") {
+            continue;
+        }
+
+        let input = fs::read_to_string(&path).unwrap();
+        let mut parser = ArseParser::new(input.as_bytes());
+        let dump = parser.debug_dump();
+
+        if expect_errors {
+            assert!(
+                !parser.errors().is_empty(),
+                "{}: expected at least one syntax error",
+                path.display()
+            );
+        } else {
+            assert!(
+                parser.errors().is_empty(),
+                "{}: unexpected syntax errors: {:?}",
+                path.display(),
+                parser.errors()
+            );
+        }
+
+        let expected_path = path.with_extension("txt");
+        let expected = fs::read_to_string(&expected_path)
+            .unwrap_or_else(|_| panic!("missing expectation file {}", expected_path.display()));
+        assert_eq!(dump, expected, "{}: dump mismatch", path.display());
+    }
+}
+
+#[test]
+fn ok_corpus() {
+    run_corpus("tests/data/ok", false);
+}
+
+#[test]
+fn err_corpus() {
+    run_corpus("tests/data/err", true);
+}